@@ -0,0 +1,53 @@
+// Command-line configuration for the solver, so runs are reproducible and
+// scriptable instead of always doing 10,000 iterations off a thread-local RNG.
+
+use clap::{Parser, ValueEnum};
+
+use crate::solver_tree::CfrVariant;
+
+#[derive(Parser, Debug)]
+#[command(about = "Solve Kuhn poker via counterfactual regret minimization")]
+pub struct Args {
+    /// Number of CFR iterations to run
+    #[arg(short = 'n', long = "num-iters", default_value_t = 10_000)]
+    pub num_iters: u32,
+
+    /// Seed for the deck-shuffling RNG, for reproducible runs
+    #[arg(short = 's', long = "seed", default_value_t = 0)]
+    pub seed: u64,
+
+    /// Format to print the solved strategy table in
+    #[arg(short = 'o', long = "output", value_enum, default_value_t = OutputFormat::Json)]
+    pub output_format: OutputFormat,
+
+    /// Also emit a per-iteration trace of the sampled deck and game value
+    #[arg(long = "trace")]
+    pub trace: bool,
+
+    /// Which regret-matching update rule to solve with
+    #[arg(long = "variant", value_enum, default_value_t = CfrVariant::Vanilla)]
+    pub variant: CfrVariant,
+
+    /// Number of worker threads. 1 (the default) runs the original
+    /// single-threaded solver; more than 1 splits each round of iterations
+    /// across threads and merges their regret/strategy updates afterwards
+    #[arg(short = 't', long = "threads", default_value_t = 1)]
+    pub threads: usize,
+
+    /// Number of iterations each round of parallel worker threads runs
+    /// before their regret/strategy-sum deltas are merged back in. Only
+    /// relevant when `--threads` is greater than 1
+    #[arg(long = "batch-size", default_value_t = 1_000)]
+    pub batch_size: u32,
+
+    /// Number of distinct card ranks in the deck. Classic Kuhn poker is
+    /// played with 3 (Jack, Queen, King); this solver originally hardcoded 5
+    #[arg(long = "num-ranks", default_value_t = 5)]
+    pub num_ranks: u8,
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+pub enum OutputFormat {
+    Debug,
+    Json,
+}
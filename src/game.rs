@@ -1,14 +1,39 @@
 // types and methods implementing core game logic
 
-#[derive(Copy, Clone, Debug, PartialOrd, Ord, PartialEq, Eq, Hash)]
-pub enum Card {
-    Ten,
-    Jack,
-    Queen,
-    King,
-    Ace,
+use serde::Serialize;
+
+/// A single rank in a `num_ranks`-rank Kuhn deck, 0 (lowest) to
+/// `num_ranks - 1` (highest). Classic Kuhn poker deals from a 3-rank deck
+/// (Jack, Queen, King); this solver's original version hardcoded a 5-rank
+/// deck. See [`Deck`] for building the full set of ranks to shuffle and
+/// deal from.
+#[derive(Copy, Clone, Debug, PartialOrd, Ord, PartialEq, Eq, Hash, Serialize)]
+pub struct Card(pub u8);
+
+/// A Kuhn-poker deck of `num_ranks` distinct cards. Only the generalized
+/// deck *size* is configurable (via [`Deck::new`]); the two-player dealing
+/// rule (each player gets one of the top two cards of a shuffled deck) is
+/// unchanged regardless of size.
+#[derive(Copy, Clone, Debug)]
+pub struct Deck {
+    num_ranks: u8,
+}
+
+impl Deck {
+    pub fn new(num_ranks: u8) -> Self {
+        assert!(
+            num_ranks >= 2,
+            "Kuhn poker needs at least 2 ranks, so the two dealt cards can differ"
+        );
+        Self { num_ranks }
+    }
+
+    /// The full set of cards in this deck, one of each rank, in ascending
+    /// order. Shuffle the result before dealing.
+    pub fn cards(&self) -> Vec<Card> {
+        (0..self.num_ranks).map(Card).collect()
+    }
 }
-pub const NUM_CARDS: usize = 5;
 
 #[cfg(test)]
 mod card_tests {
@@ -16,19 +41,19 @@ mod card_tests {
 
     #[test]
     fn card_eq() {
-        assert_eq!(Card::King, Card::King);
-        assert_ne!(Card::Ace, Card::Queen);
+        assert_eq!(Card(3), Card(3));
+        assert_ne!(Card(4), Card(2));
     }
 
     #[test]
     fn card_ord() {
-        assert!(Card::Ace > Card::Queen);
-        assert!(Card::King < Card::Ace);
-        assert!(Card::Queen <= Card::King);
+        assert!(Card(4) > Card(2));
+        assert!(Card(3) < Card(4));
+        assert!(Card(2) <= Card(3));
     }
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[derive(Copy, Clone, Debug, PartialOrd, Ord, PartialEq, Eq, Hash, Serialize)]
 pub enum Move {
     Check,
     Bet,
@@ -37,7 +62,7 @@ pub enum Move {
     Fold,
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize)]
 pub enum Player {
     Player0,
     Player1,
@@ -50,14 +75,14 @@ pub fn other_player(p: Player) -> Player {
     }
 }
 
-pub fn get_player_card(p: Player, deck: &[Card; NUM_CARDS]) -> Card {
+pub fn get_player_card(p: Player, deck: &[Card]) -> Card {
     match p {
         Player::Player0 => deck[0],
         Player::Player1 => deck[1],
     }
 }
 
-pub fn winning_player(deck: &[Card; NUM_CARDS]) -> Player {
+pub fn winning_player(deck: &[Card]) -> Player {
     let card0 = get_player_card(Player::Player0, deck);
     let card1 = get_player_card(Player::Player1, deck);
     if card0 > card1 {
@@ -0,0 +1,85 @@
+// Serialization of solved strategies and per-iteration game traces to JSON,
+// so results can be diffed, plotted, or loaded by a viewer instead of only
+// being read off the terminal as Debug output.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Write};
+
+use serde::Serialize;
+
+use crate::game::Card;
+use crate::game::Move;
+use crate::solver_tree::{Floating, InfoSetHash, NodeInfo};
+
+/// The solved average strategy at a single (non-terminal) info set.
+#[derive(Serialize)]
+pub struct StrategyRecord {
+    card: Card,
+    history: Vec<Move>,
+    avg_strategy: BTreeMap<Move, Floating>,
+}
+
+impl StrategyRecord {
+    fn new(node_info: &NodeInfo) -> Self {
+        let info_set = node_info.info_set();
+        let legal_moves = info_set.get_next_moves();
+        Self {
+            card: info_set.card(),
+            history: info_set.move_history(),
+            avg_strategy: node_info.get_average_strategy(&legal_moves).into_iter().collect(),
+        }
+    }
+}
+
+/// A record of a single CFR iteration: which deal was sampled, the
+/// `ChancyHistory` path sampled from the current strategy, and the
+/// resulting game value for that iteration.
+#[derive(Serialize)]
+pub struct TraceRecord {
+    iteration: usize,
+    deck: Vec<Card>,
+    path: Vec<Move>,
+    utility: Floating,
+}
+
+impl TraceRecord {
+    pub fn new(iteration: usize, deck: &[Card], path: Vec<Move>, utility: Floating) -> Self {
+        Self {
+            iteration,
+            deck: deck.to_vec(),
+            path,
+            utility,
+        }
+    }
+}
+
+/// Build the strategy table (one record per non-terminal info set) from a
+/// solved `node_map`, sorted by `(history, card)` so that identical-seed
+/// runs produce byte-identical output instead of reflecting `HashMap`'s
+/// randomized iteration order.
+pub fn strategy_table(node_map: &HashMap<InfoSetHash, NodeInfo>) -> Vec<StrategyRecord> {
+    let mut table: Vec<StrategyRecord> = node_map
+        .values()
+        .filter(|node_info| !node_info.info_set().is_terminal())
+        .map(StrategyRecord::new)
+        .collect();
+    table.sort_by(|a, b| (&a.history, &a.card).cmp(&(&b.history, &b.card)));
+    table
+}
+
+/// Write the solved strategy table as pretty-printed JSON.
+pub fn write_strategy_table<W: Write>(
+    writer: W,
+    node_map: &HashMap<InfoSetHash, NodeInfo>,
+) -> serde_json::Result<()> {
+    serde_json::to_writer_pretty(writer, &strategy_table(node_map))
+}
+
+/// Write a per-iteration game trace as pretty-printed JSON.
+pub fn write_trace<W: Write>(writer: W, trace: &[TraceRecord]) -> serde_json::Result<()> {
+    serde_json::to_writer_pretty(writer, trace)
+}
+
+pub fn stdout_writer() -> io::BufWriter<io::Stdout> {
+    io::BufWriter::new(io::stdout())
+}
@@ -7,42 +7,93 @@
 // then maybe do abstract info sets
 // then do a full poker solver (def with abstract info sets, might have to do it monte carlo)
 
-use rand::Rng;
+use clap::Parser;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use std::collections::HashMap;
 use std::time::SystemTime;
 
-use crate::game::{Card, NUM_CARDS};
-use crate::solver_tree::{ChancyHistory, Floating, InfoSet, NodeInfo, NodeUtils};
+use crate::cli::{Args, OutputFormat};
+use crate::game::{Card, Deck, Move, Player};
+use crate::json_output::TraceRecord;
+use crate::solver_tree::{CfrVariant, ChancyHistory, Floating, InfoSetHash, NodeInfo, NodeUtils};
 
+mod cli;
 mod game;
+mod json_output;
+mod parallel;
 mod solver_tree;
 
 fn main() {
-    let num_iters = 10_000;
-    let mut rng = rand::thread_rng();
-    let mut deck: [Card; NUM_CARDS] = [Card::Ace, Card::King, Card::Queen, Card::Jack, Card::Ten];
-
-    let mut util = 0.0;
-    let mut node_map: HashMap<InfoSet, NodeInfo> = HashMap::new();
+    let args = Args::parse();
+    let num_iters = args.num_iters;
 
     let start = SystemTime::now();
 
-    for _ in 0..num_iters {
-        shuffle_deck(&mut deck, &mut rng);
-        util += cfr(&deck, &mut node_map);
+    // A per-iteration trace isn't supported in multi-threaded mode, since
+    // iterations run out of order across threads rather than one at a time.
+    // Rather than silently dropping the flag, warn about the combination so a
+    // user asking for a trace learns why no trace output follows; the solve
+    // itself still runs.
+    if args.trace && args.threads > 1 {
+        eprintln!("Warning: --trace is not supported with --threads > 1 (iterations run out of order across threads); no trace will be emitted.");
     }
 
+    let (node_map, util, trace) = if args.threads > 1 {
+        let (node_map, util) = parallel::run(
+            num_iters,
+            args.threads,
+            args.batch_size,
+            args.seed,
+            args.variant,
+            args.num_ranks,
+        );
+        (node_map, util, Vec::new())
+    } else {
+        let mut rng = StdRng::seed_from_u64(args.seed);
+        let mut deck = Deck::new(args.num_ranks).cards();
+        let mut util = 0.0;
+        let mut node_map: HashMap<InfoSetHash, NodeInfo> = HashMap::new();
+        let mut trace: Vec<TraceRecord> = Vec::new();
+
+        for i in 0..num_iters {
+            shuffle_deck(&mut deck, &mut rng);
+            let iter_util = cfr(&deck, &mut node_map, (i + 1) as Floating, args.variant);
+            util += iter_util;
+            if args.trace {
+                let path = sample_path(&deck, &node_map, &mut rng);
+                trace.push(TraceRecord::new(i as usize, &deck, path, iter_util));
+            }
+        }
+        (node_map, util, trace)
+    };
+
     println!("Average game value is {}", util / (num_iters as Floating));
-    for (info_set, node_info) in node_map.into_iter() {
-        if !info_set.is_terminal() {
-            let legal_moves = info_set.get_next_moves();
-            let avg_strategy = node_info.get_average_strategy(&legal_moves);
-            println!(
-                "At info_set {:?}, avg strategy is {:?}",
-                info_set, avg_strategy
-            );
+    match args.output_format {
+        OutputFormat::Debug => {
+            for node_info in node_map.values() {
+                let info_set = node_info.info_set();
+                if !info_set.is_terminal() {
+                    let legal_moves = info_set.get_next_moves();
+                    let avg_strategy = node_info.get_average_strategy(&legal_moves);
+                    println!(
+                        "At info_set {:?}, avg strategy is {:?}",
+                        info_set, avg_strategy
+                    );
+                }
+            }
+        }
+        OutputFormat::Json => {
+            json_output::write_strategy_table(json_output::stdout_writer(), &node_map)
+                .expect("Writing the strategy table to stdout should not fail");
+            println!();
         }
     }
+    if args.trace && args.threads <= 1 {
+        json_output::write_trace(json_output::stdout_writer(), &trace)
+            .expect("Writing the game trace to stdout should not fail");
+        println!();
+    }
 
     match start.elapsed() {
         Ok(elapsed) => {
@@ -56,87 +107,142 @@ fn main() {
     println!("Number of iterations: {}", num_iters);
 }
 
-fn shuffle_deck(deck: &mut [Card; NUM_CARDS], rng: &mut rand::rngs::ThreadRng) {
-    for i in (1..NUM_CARDS).rev() {
+// Under alternating updates, iteration 1 updates Player0, iteration 2 updates
+// Player1, and so on.
+fn is_traversing_player(iteration: Floating, player: Player) -> bool {
+    let iteration_parity = (iteration as u64) % 2;
+    match player {
+        Player::Player0 => iteration_parity == 1,
+        Player::Player1 => iteration_parity == 0,
+    }
+}
+
+fn shuffle_deck<R: Rng>(deck: &mut [Card], rng: &mut R) {
+    for i in (1..deck.len()).rev() {
         let j = rng.gen_range(0..(i + 1));
         deck.swap(i, j);
     }
 }
 
-fn cfr(deck: &[Card; NUM_CARDS], node_map: &mut HashMap<InfoSet, NodeInfo>) -> Floating {
-    let mut utils_map: HashMap<InfoSet, NodeUtils> = HashMap::new();
+/// Walk a single sampled `ChancyHistory` path from the root to a terminal
+/// node, drawing each move from that info set's current strategy. Used to
+/// give `--trace` something concrete to show for an iteration, alongside
+/// the sampled deck and the resulting utility: this solver otherwise
+/// updates every node in the tree at once rather than following one path.
+fn sample_path<R: Rng>(
+    deck: &[Card],
+    node_map: &HashMap<InfoSetHash, NodeInfo>,
+    rng: &mut R,
+) -> Vec<Move> {
+    let mut chancy_hist = ChancyHistory::new();
+    let mut path = Vec::new();
+
+    while !chancy_hist.is_terminal() {
+        let node_hash = chancy_hist.to_info_set_hash(deck);
+        let node_info = node_map
+            .get(&node_hash)
+            .expect("Every node reachable under the current strategy should already be in the map");
+        let legal_moves = node_info.info_set().get_next_moves();
+
+        let sample: Floating = rng.gen();
+        let mut cumulative = 0.0;
+        let chosen = *legal_moves
+            .iter()
+            .find(|m| {
+                cumulative += node_info.get_strategy(**m);
+                sample < cumulative
+            })
+            .unwrap_or(legal_moves.last().expect("Non-terminal nodes have at least one legal move"));
+
+        let prob = node_info.get_strategy(chosen);
+        chancy_hist = chancy_hist.extend(chosen, prob).unwrap();
+        path.push(chosen);
+    }
+
+    path
+}
+
+fn cfr(
+    deck: &[Card],
+    node_map: &mut HashMap<InfoSetHash, NodeInfo>,
+    iteration: Floating,
+    variant: CfrVariant,
+) -> Floating {
+    let mut utils_map: HashMap<InfoSetHash, NodeUtils> = HashMap::new();
 
     // Get a topological ordering of the game tree
     let top_order = get_topological_ordering(deck, node_map);
 
     // Iterate thru nodes in reverse topological order, so we can propagate values up the tree.
     for chancy_hist in top_order.into_iter().rev() {
-        let info_set = chancy_hist.to_info_set(deck);
+        let node_hash = chancy_hist.to_info_set_hash(deck);
         let node_info = node_map
-            .get_mut(&info_set)
+            .get_mut(&node_hash)
             .expect("Entries should have been added to node map during topological sort");
-        utils_map.insert(
-            chancy_hist.to_info_set(deck),
-            NodeUtils::new(&info_set.get_next_moves()),
-        );
+        let legal_moves = node_info.info_set().get_next_moves();
+        utils_map.insert(node_hash, NodeUtils::new(&legal_moves));
 
         if let Some(u) = chancy_hist.util_if_terminal(deck) {
             // chancy_hist is terminal
             // No need to set move utils here
             // but we do need to say what the value of the node is for backwards induction.
-            let node_utils = utils_map.get_mut(&info_set).unwrap();
+            let node_utils = utils_map.get_mut(&node_hash).unwrap();
             node_utils.value = u;
             // No need to calculate counterfactual regrets or update strategies for a terminal node.
         } else {
             // First, set move utilities by the values of the successor nodes
-            let legal_moves = info_set.get_next_moves();
             for m in &legal_moves {
                 let prob_move = node_info.get_strategy(*m);
                 let next_chancy_hist = chancy_hist.extend(*m, prob_move).unwrap();
-                let next_info_set = next_chancy_hist.to_info_set(deck);
-                let next_node_value = utils_map.get(&next_info_set).expect("Utils should have been set earlier in the loop, because we're iterating thru the reverse of a topological sort").value;
+                let next_hash = next_chancy_hist.to_info_set_hash(deck);
+                let next_node_value = utils_map.get(&next_hash).expect("Utils should have been set earlier in the loop, because we're iterating thru the reverse of a topological sort").value;
                 let node_utils = utils_map
-                    .get_mut(&info_set)
+                    .get_mut(&node_hash)
                     .expect("We should have created this entry at the start of this loop");
-                node_utils.move_utils.insert(*m, (-1.0) * next_node_value);
-                node_utils.value += prob_move * (-1.0) * next_node_value;
+                node_utils.move_utils.insert(*m, -next_node_value);
+                node_utils.value += -prob_move * next_node_value;
             }
 
             // Next, calculate counterfactual regrets and update the regret sums.
-            let node_utils = utils_map
-                .get_mut(&info_set)
-                .expect("We should have created this entry at the start of this loop");
-            for m in &legal_moves {
-                let util_m = node_utils
-                    .move_utils
-                    .get(m)
-                    .expect("We should have just calculated utils for all moves");
-                let regret_m = util_m - node_utils.value;
-                let counterfact_prob = chancy_hist.get_counterfactual_reach_prob();
-                node_info.update_regret(*m, counterfact_prob * regret_m);
-            }
+            // Under CFR+, only the player whose turn it is this iteration gets
+            // updated (alternating updates), which is the standard way CFR+ is run.
+            let should_update = variant == CfrVariant::Vanilla
+                || is_traversing_player(iteration, chancy_hist.player_to_move());
+            if should_update {
+                let node_utils = utils_map
+                    .get_mut(&node_hash)
+                    .expect("We should have created this entry at the start of this loop");
+                for m in &legal_moves {
+                    let util_m = node_utils
+                        .move_utils
+                        .get(m)
+                        .expect("We should have just calculated utils for all moves");
+                    let regret_m = util_m - node_utils.value;
+                    let counterfact_prob = chancy_hist.get_counterfactual_reach_prob();
+                    node_info.update_regret(*m, counterfact_prob * regret_m, variant);
+                }
 
-            // Finally, update strategies.
-            let reach_prob = chancy_hist.get_reach_prob();
-            node_info.update_strategy(&legal_moves, reach_prob);
+                // Finally, update strategies.
+                let reach_prob = chancy_hist.get_reach_prob();
+                node_info.update_strategy(&legal_moves, reach_prob, iteration, variant);
+            }
         }
     }
 
     // return the utility of the start node
     let start_node = ChancyHistory::new();
     utils_map
-        .get(&start_node.to_info_set(deck))
+        .get(&start_node.to_info_set_hash(deck))
         .expect("We should have calculated info for this node in the main loop")
         .value
 }
 
 fn append_children_to_stack(
     chancy_hist: &ChancyHistory,
-    info_set: &InfoSet,
     node_info: &NodeInfo,
     node_stack: &mut Vec<ChancyHistory>,
 ) {
-    let legal_moves = info_set.get_next_moves();
+    let legal_moves = node_info.info_set().get_next_moves();
     for m in legal_moves {
         // get prob of taking m from this info set
         let prob_move = node_info.get_strategy(m);
@@ -146,23 +252,77 @@ fn append_children_to_stack(
 }
 
 fn get_topological_ordering(
-    deck: &[Card; NUM_CARDS],
-    node_map: &mut HashMap<InfoSet, NodeInfo>,
+    deck: &[Card],
+    node_map: &mut HashMap<InfoSetHash, NodeInfo>,
 ) -> Vec<ChancyHistory> {
     let mut unseen_nodes = vec![ChancyHistory::new()];
     let mut ordered_nodes: Vec<ChancyHistory> = Vec::new();
 
     while let Some(chancy_hist) = unseen_nodes.pop() {
-        let info_set = chancy_hist.to_info_set(deck);
-        let legal_moves = info_set.get_next_moves();
-        let node_info = node_map
-            .entry(info_set.clone())
-            .or_insert(NodeInfo::new(&legal_moves));
+        let node_hash = chancy_hist.to_info_set_hash(deck);
+        let node_info = node_map.entry(node_hash).or_insert_with(|| {
+            let info_set = chancy_hist.to_info_set(deck);
+            let legal_moves = info_set.get_next_moves();
+            NodeInfo::new(info_set, &legal_moves)
+        });
         if !chancy_hist.is_terminal() {
-            append_children_to_stack(&chancy_hist, &info_set, node_info, &mut unseen_nodes);
+            append_children_to_stack(&chancy_hist, node_info, &mut unseen_nodes);
         }
         ordered_nodes.push(chancy_hist);
     }
 
     ordered_nodes
 }
+
+#[cfg(test)]
+mod cfr_tests {
+    use super::*;
+    use crate::solver_tree::CfrVariant;
+
+    // Solving classic 3-rank Kuhn poker should recover the shape of the
+    // textbook equilibrium described in Neller & Lanctot section 3.1: the
+    // Queen never opens with a bet, the King bets three times as often as
+    // the Jack bluffs, and the Jack's bluff frequency (alpha) falls
+    // somewhere in [0, 1/3]. CFR doesn't pin down which alpha a given
+    // run converges to (vanilla and CFR+ land on different points along
+    // that line), so unlike the other moves this repo solves for, there's
+    // no single target frequency to assert against directly.
+    #[test]
+    fn three_rank_kuhn_poker_recovers_textbook_equilibrium_shape() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut deck = Deck::new(3).cards();
+        let mut node_map: HashMap<InfoSetHash, NodeInfo> = HashMap::new();
+
+        for i in 0..100_000 {
+            shuffle_deck(&mut deck, &mut rng);
+            cfr(&deck, &mut node_map, (i + 1) as Floating, CfrVariant::CfrPlus);
+        }
+
+        let opening_bet_freq = |card: u8| {
+            let other_card = (card + 1) % 3;
+            let hash = ChancyHistory::new().to_info_set_hash(&[Card(card), Card(other_card)]);
+            let node_info = node_map
+                .get(&hash)
+                .expect("The opening node for every card should have been visited");
+            let legal_moves = node_info.info_set().get_next_moves();
+            node_info.get_average_strategy(&legal_moves)[&Move::Bet]
+        };
+
+        let jack_bet = opening_bet_freq(0);
+        let queen_bet = opening_bet_freq(1);
+        let king_bet = opening_bet_freq(2);
+
+        assert!(
+            queen_bet < 0.03,
+            "expected the Queen to (almost) never open with a bet, got {queen_bet}"
+        );
+        assert!(
+            (0.0..=1.0 / 3.0 + 0.03).contains(&jack_bet),
+            "expected the Jack's bluff frequency to lie in [0, 1/3], got {jack_bet}"
+        );
+        assert!(
+            (king_bet - 3.0 * jack_bet).abs() < 0.15,
+            "expected King-bet to be about 3x Jack-bet, got king={king_bet} jack={jack_bet}"
+        );
+    }
+}
@@ -0,0 +1,223 @@
+// Multi-threaded CFR: run batches of sampled deals concurrently against a
+// read-only snapshot of the current strategies, then merge each thread's
+// regret and strategy-sum deltas into the shared node map between rounds.
+//
+// This mirrors the single-threaded `cfr` in main.rs, except strategy lookups
+// go through a frozen snapshot instead of a live `&mut HashMap`, and regret
+// and strategy-sum updates are accumulated into thread-local delta maps
+// instead of being applied in place.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use crate::game::{Card, Deck, Move};
+use crate::solver_tree::{
+    splitmix64, CfrVariant, ChancyHistory, Floating, InfoSetHash, NodeInfo, NodeUtils,
+};
+use crate::{is_traversing_player, shuffle_deck};
+
+/// Run `num_iters` CFR iterations spread across `num_threads` worker
+/// threads, synchronizing every `batch_size` iterations. Returns the solved
+/// node map and the summed game value across all iterations.
+pub fn run(
+    num_iters: u32,
+    num_threads: usize,
+    batch_size: u32,
+    seed: u64,
+    variant: CfrVariant,
+    num_ranks: u8,
+) -> (HashMap<InfoSetHash, NodeInfo>, Floating) {
+    let mut node_map: HashMap<InfoSetHash, NodeInfo> = HashMap::new();
+    let mut util = 0.0;
+    let mut round_start = 0;
+
+    while round_start < num_iters {
+        let round_len = batch_size.min(num_iters - round_start);
+        let snapshot = Arc::new(node_map.clone());
+        let per_thread = (round_len as usize).div_ceil(num_threads).max(1);
+
+        let batch_results = crossbeam::thread::scope(|scope| {
+            let mut handles = Vec::new();
+            for worker in 0..num_threads {
+                let lo = (worker * per_thread) as u32;
+                let hi = (((worker + 1) * per_thread) as u32).min(round_len);
+                if lo >= hi {
+                    continue;
+                }
+                let snapshot = Arc::clone(&snapshot);
+                // Mixing round_start and worker through splitmix64, rather
+                // than adding them to the seed, gives each worker in each
+                // round a seed spread over the whole u64 range instead of
+                // one of a run of consecutive seeds.
+                let worker_seed = splitmix64(splitmix64(seed ^ (round_start as u64)) ^ (worker as u64));
+                handles.push(scope.spawn(move |_| {
+                    run_batch(&snapshot, round_start, lo, hi, worker_seed, variant, num_ranks)
+                }));
+            }
+            handles
+                .into_iter()
+                .map(|h| h.join().expect("CFR worker thread should not panic"))
+                .collect::<Vec<_>>()
+        })
+        .expect("Worker thread scope should not panic");
+
+        for (deltas, batch_util) in batch_results {
+            util += batch_util;
+            for (node_hash, delta) in deltas {
+                node_map
+                    .entry(node_hash)
+                    .and_modify(|existing| existing.merge(&delta))
+                    .or_insert(delta);
+            }
+        }
+
+        // Now that this round's deltas are merged in, apply the CFR+ regret
+        // floor and refresh every node's cached regret-matching strategy for
+        // the next round's snapshot.
+        for node_info in node_map.values_mut() {
+            let info_set = node_info.info_set();
+            if !info_set.is_terminal() {
+                let legal_moves = info_set.get_next_moves();
+                node_info.finish_round(&legal_moves, variant);
+            }
+        }
+
+        round_start += round_len;
+    }
+
+    (node_map, util)
+}
+
+/// Run one worker's share `[lo, hi)` of a round's iterations, against the
+/// frozen `snapshot` of strategies as of the start of the round.
+fn run_batch(
+    snapshot: &HashMap<InfoSetHash, NodeInfo>,
+    round_start: u32,
+    lo: u32,
+    hi: u32,
+    seed: u64,
+    variant: CfrVariant,
+    num_ranks: u8,
+) -> (HashMap<InfoSetHash, NodeInfo>, Floating) {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut deck = Deck::new(num_ranks).cards();
+    let mut deltas: HashMap<InfoSetHash, NodeInfo> = HashMap::new();
+    let mut util = 0.0;
+
+    for offset in lo..hi {
+        let iteration = (round_start + offset + 1) as Floating;
+        shuffle_deck(&mut deck, &mut rng);
+        util += cfr_sample(&deck, snapshot, &mut deltas, iteration, variant);
+    }
+
+    (deltas, util)
+}
+
+fn snapshot_strategy(
+    snapshot: &HashMap<InfoSetHash, NodeInfo>,
+    node_hash: InfoSetHash,
+    legal_moves: &[Move],
+    m: Move,
+) -> Floating {
+    match snapshot.get(&node_hash) {
+        Some(node_info) => node_info.get_strategy(m),
+        None => 1.0 / (legal_moves.len() as Floating),
+    }
+}
+
+fn topological_order(
+    deck: &[Card],
+    snapshot: &HashMap<InfoSetHash, NodeInfo>,
+) -> Vec<ChancyHistory> {
+    let mut unseen_nodes = vec![ChancyHistory::new()];
+    let mut ordered_nodes = Vec::new();
+
+    while let Some(chancy_hist) = unseen_nodes.pop() {
+        let node_hash = chancy_hist.to_info_set_hash(deck);
+        if !chancy_hist.is_terminal() {
+            let legal_moves = chancy_hist.to_info_set(deck).get_next_moves();
+            for m in legal_moves.clone() {
+                let prob_move = snapshot_strategy(snapshot, node_hash, &legal_moves, m);
+                let next_chancy_hist = chancy_hist.extend(m, prob_move).unwrap();
+                unseen_nodes.push(next_chancy_hist);
+            }
+        }
+        ordered_nodes.push(chancy_hist);
+    }
+
+    ordered_nodes
+}
+
+/// One sampled-deal pass of CFR, reading strategies from `snapshot` and
+/// writing regret/strategy-sum deltas into `deltas`, rather than mutating a
+/// shared node map in place as the single-threaded `cfr` does.
+fn cfr_sample(
+    deck: &[Card],
+    snapshot: &HashMap<InfoSetHash, NodeInfo>,
+    deltas: &mut HashMap<InfoSetHash, NodeInfo>,
+    iteration: Floating,
+    variant: CfrVariant,
+) -> Floating {
+    let mut utils_map: HashMap<InfoSetHash, NodeUtils> = HashMap::new();
+    let top_order = topological_order(deck, snapshot);
+
+    for chancy_hist in top_order.into_iter().rev() {
+        let node_hash = chancy_hist.to_info_set_hash(deck);
+        let info_set = chancy_hist.to_info_set(deck);
+        let legal_moves = info_set.get_next_moves();
+        utils_map.insert(node_hash, NodeUtils::new(&legal_moves));
+
+        if let Some(u) = chancy_hist.util_if_terminal(deck) {
+            utils_map.get_mut(&node_hash).unwrap().value = u;
+        } else {
+            for m in &legal_moves {
+                let prob_move = snapshot_strategy(snapshot, node_hash, &legal_moves, *m);
+                let next_chancy_hist = chancy_hist.extend(*m, prob_move).unwrap();
+                let next_hash = next_chancy_hist.to_info_set_hash(deck);
+                let next_value = utils_map
+                    .get(&next_hash)
+                    .expect("Utils should have been set earlier in the loop, because we're iterating thru the reverse of a topological sort")
+                    .value;
+                let node_utils = utils_map.get_mut(&node_hash).unwrap();
+                node_utils.move_utils.insert(*m, -next_value);
+                node_utils.value += -prob_move * next_value;
+            }
+
+            let should_update = variant == CfrVariant::Vanilla
+                || is_traversing_player(iteration, chancy_hist.player_to_move());
+            if should_update {
+                let node_utils = utils_map.get(&node_hash).unwrap();
+                let counterfact_prob = chancy_hist.get_counterfactual_reach_prob();
+                let reach_prob = chancy_hist.get_reach_prob();
+                let avg_weight = match variant {
+                    CfrVariant::Vanilla => 1.0,
+                    CfrVariant::CfrPlus => iteration,
+                };
+                let delta = deltas
+                    .entry(node_hash)
+                    .or_insert_with(|| NodeInfo::new(info_set.clone(), &legal_moves));
+                for m in &legal_moves {
+                    let util_m = node_utils
+                        .move_utils
+                        .get(m)
+                        .expect("We should have just calculated utils for all moves");
+                    let regret_m = util_m - node_utils.value;
+                    delta.add_regret(*m, counterfact_prob * regret_m);
+                }
+                for m in &legal_moves {
+                    let strat_m = snapshot_strategy(snapshot, node_hash, &legal_moves, *m);
+                    delta.add_strategy_sum(*m, avg_weight * reach_prob * strat_m);
+                }
+            }
+        }
+    }
+
+    let start_node = ChancyHistory::new();
+    utils_map
+        .get(&start_node.to_info_set_hash(deck))
+        .expect("We should have calculated info for this node in the main loop")
+        .value
+}
@@ -2,11 +2,76 @@
 // Includes relevant methods.
 
 use std::collections::HashMap;
+use std::sync::OnceLock;
 
-use crate::game::{get_player_card, other_player, winning_player, Card, Move, Player, NUM_CARDS};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::game::{get_player_card, other_player, winning_player, Card, Move, Player};
 
 pub type Floating = f64;
 
+/// The Zobrist hash of an `InfoSet`: a `card` XORed with a history of
+/// `Move`s, collapsed to a single `u64`. Used to key `node_map`/`utils_map`
+/// instead of the full `InfoSet` (which otherwise has to be hashed, and in
+/// the case of a cache miss cloned, move-history `Vec` and all, on every
+/// lookup).
+///
+/// Collisions are possible in principle (two distinct info sets hashing to
+/// the same `u64`) but are astronomically unlikely for a game tree this
+/// small against 64 bits of random key material, so we accept the risk
+/// rather than store the full key alongside it.
+pub type InfoSetHash = u64;
+
+const NUM_MOVE_KINDS: usize = 5;
+// Kuhn poker (even the generalized, multi-raise variants this solver might
+// grow into) produces short histories; this bounds how deep the Zobrist
+// table goes before `ChancyHistory::extend` starts panicking.
+const MAX_HISTORY_LEN: usize = 8;
+
+struct ZobristKeys {
+    move_keys: [[u64; NUM_MOVE_KINDS]; MAX_HISTORY_LEN],
+}
+
+fn move_index(m: Move) -> usize {
+    m as usize
+}
+
+fn zobrist_keys() -> &'static ZobristKeys {
+    static KEYS: OnceLock<ZobristKeys> = OnceLock::new();
+    KEYS.get_or_init(|| {
+        // The seed is fixed (rather than drawn from the run's own RNG) since
+        // these keys just need to be pairwise random enough to make
+        // collisions unlikely; they don't need to vary between runs, and
+        // keeping them fixed makes hashes comparable across runs too.
+        let mut rng = StdRng::seed_from_u64(0x5a0b_1e55_u64);
+        let mut move_keys = [[0u64; NUM_MOVE_KINDS]; MAX_HISTORY_LEN];
+        for ply in move_keys.iter_mut() {
+            for key in ply.iter_mut() {
+                *key = rng.gen();
+            }
+        }
+        ZobristKeys { move_keys }
+    })
+}
+
+/// Zobrist key for a single card rank. Unlike `move_keys`, this isn't
+/// drawn from a precomputed table sized to the deck: since the solver now
+/// supports any deck size (see `Deck`), the key is mixed deterministically
+/// from the rank itself, so it's defined for however many ranks a run asks
+/// for.
+fn card_zobrist_key(card: Card) -> u64 {
+    const CARD_KEY_SEED: u64 = 0x9e37_79b9_7f4a_7c15;
+    splitmix64(CARD_KEY_SEED ^ (card.0 as u64))
+}
+
+pub(crate) fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
 #[derive(Debug)]
 enum HistState {
     InProgress,
@@ -69,6 +134,10 @@ impl History {
 pub struct ChancyHistory {
     player_to_move: Player,
     moves_and_counterfactual_reach_probs: Vec<((Floating, Floating), Move)>,
+    // Incremental Zobrist hash of `moves_and_counterfactual_reach_probs`'s
+    // moves, updated by a single XOR in `extend` rather than recomputed from
+    // scratch on every lookup.
+    hash: u64,
 }
 
 #[cfg(test)]
@@ -107,13 +176,28 @@ mod chancy_hist_tests {
         assert_eq!(chancy_hist_2.get_counterfactual_reach_prob(), 0.7);
     }
 
+    #[test]
+    fn incremental_hash_matches_from_scratch() {
+        let moves = [Move::Check, Move::Bet, Move::Raise];
+        let chancy_hist = moves.iter().fold(ChancyHistory::new(), |hist, &m| {
+            hist.extend(m, 0.5).unwrap()
+        });
+
+        let expected_hash = moves.iter().enumerate().fold(0u64, |acc, (ply, &m)| {
+            acc ^ crate::solver_tree::zobrist_keys().move_keys[ply]
+                [crate::solver_tree::move_index(m)]
+        });
+
+        assert_eq!(chancy_hist.history_hash(), expected_hash);
+    }
+
     #[test]
     fn right_terminal_utilities() {
         let chancy_hist_0 = ChancyHistory::new();
         let chancy_hist_1 = chancy_hist_0.extend(Move::Check, 0.5).unwrap();
         let chancy_hist_2 = chancy_hist_1.extend(Move::Bet, 0.5).unwrap();
         let chancy_hist_3 = chancy_hist_2.extend(Move::Call, 0.5).unwrap();
-        let deck = [Card::Ace, Card::King, Card::Queen, Card::Jack, Card::Ten];
+        let deck = [Card(4), Card(3), Card(2), Card(1), Card(0)];
         assert_eq!(chancy_hist_2.util_if_terminal(&deck), None);
         assert_eq!(chancy_hist_3.util_if_terminal(&deck), Some(-2.0));
     }
@@ -124,6 +208,7 @@ impl ChancyHistory {
         Self {
             player_to_move: Player::Player0,
             moves_and_counterfactual_reach_probs: Vec::new(),
+            hash: 0,
         }
     }
 
@@ -139,21 +224,34 @@ impl ChancyHistory {
         }
     }
 
-    pub fn to_info_set(&self, deck: &[Card; NUM_CARDS]) -> InfoSet {
+    pub fn to_info_set(&self, deck: &[Card]) -> InfoSet {
         let history = self.determinize();
         let card = get_player_card(self.player_to_move, deck);
         InfoSet { card, history }
     }
 
+    /// The Zobrist hash of the `InfoSet` this history belongs to, for the
+    /// player about to move: the history's incremental hash XORed with that
+    /// player's card. Cheaper than `to_info_set` when only a map key is
+    /// needed, since it doesn't clone the move history.
+    pub fn to_info_set_hash(&self, deck: &[Card]) -> InfoSetHash {
+        let card = get_player_card(self.player_to_move, deck);
+        self.hash ^ card_zobrist_key(card)
+    }
+
     pub fn len(&self) -> usize {
         self.moves_and_counterfactual_reach_probs.len()
     }
 
+    pub fn player_to_move(&self) -> Player {
+        self.player_to_move
+    }
+
     pub fn is_terminal(&self) -> bool {
         !matches!(self.determinize().termination_type(), HistState::InProgress)
     }
 
-    pub fn util_if_terminal(&self, deck: &[Card; NUM_CARDS]) -> Option<Floating> {
+    pub fn util_if_terminal(&self, deck: &[Card]) -> Option<Floating> {
         // get the utility of terminal histories, return None if not terminal.
         let current_player_winning = winning_player(deck) == self.player_to_move;
         let has_raise = self.determinize().moves.contains(&Move::Raise);
@@ -189,14 +287,28 @@ impl ChancyHistory {
                 Player::Player1 => (prob_0, prob_1 * prob),
             }
         };
+        assert!(
+            length < MAX_HISTORY_LEN,
+            "History longer than the precomputed Zobrist table supports"
+        );
+        let new_hash = self.hash ^ zobrist_keys().move_keys[length][move_index(m)];
         let mut new_moves_probs = self.moves_and_counterfactual_reach_probs.clone();
         new_moves_probs.push((counterfac_probs, m));
         Some(Self {
             player_to_move: new_player,
             moves_and_counterfactual_reach_probs: new_moves_probs,
+            hash: new_hash,
         })
     }
 
+    /// The raw incremental move-history hash, with no card XORed in. Exposed
+    /// only so `chancy_hist_tests` can check it against an independently
+    /// recomputed hash; real callers go through `to_info_set_hash`.
+    #[cfg(test)]
+    pub fn history_hash(&self) -> u64 {
+        self.hash
+    }
+
     pub fn get_reach_prob(&self) -> Floating {
         // returns the probability of reaching this history
         let length = self.len();
@@ -238,23 +350,53 @@ impl InfoSet {
     pub fn get_next_moves(&self) -> Vec<Move> {
         self.history.next_moves()
     }
+
+    pub fn card(&self) -> Card {
+        self.card
+    }
+
+    pub fn move_history(&self) -> Vec<Move> {
+        self.history.moves.clone()
+    }
 }
 
+/// Which regret-matching update rule to use.
+///
+/// `CfrPlus` addresses the "weight early iterations less" item in the
+/// original TODO: it floors cumulative regret at zero so a move that was
+/// bad early on isn't permanently suppressed, and it weights the strategy
+/// average linearly by iteration number, both of which make it converge
+/// markedly faster than vanilla CFR.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum CfrVariant {
+    Vanilla,
+    CfrPlus,
+}
+
+#[derive(Clone)]
 pub struct NodeInfo {
+    // Kept only so the info set can be displayed (it's no longer the map
+    // key node_map is indexed by; see `InfoSetHash`).
+    info_set: InfoSet,
     regret_sum: HashMap<Move, Floating>,
     strategy: HashMap<Move, Floating>,
     strategy_sum: HashMap<Move, Floating>,
 }
 
 impl NodeInfo {
-    pub fn new(legal_moves: &Vec<Move>) -> Self {
+    pub fn new(info_set: InfoSet, legal_moves: &Vec<Move>) -> Self {
         Self {
+            info_set,
             regret_sum: new_move_to_float_map_zeros(legal_moves),
             strategy: new_move_to_float_map_probs(legal_moves),
             strategy_sum: new_move_to_float_map_zeros(legal_moves),
         }
     }
 
+    pub fn info_set(&self) -> &InfoSet {
+        &self.info_set
+    }
+
     pub fn get_strategy(&self, m: Move) -> Floating {
         *self
             .strategy
@@ -262,29 +404,35 @@ impl NodeInfo {
             .expect("All nodes that exist should have strategies, and we should only call get_strategy on legal moves")
     }
 
-    pub fn update_regret(&mut self, m: Move, r: Floating) {
-        *self.regret_sum.get_mut(&m).expect(
+    pub fn update_regret(&mut self, m: Move, r: Floating, variant: CfrVariant) {
+        let regret_sum = self.regret_sum.get_mut(&m).expect(
             "We should only call update_regret on nodes that have regret sums, and on legal moves",
-        ) += r;
+        );
+        *regret_sum += r;
+        if variant == CfrVariant::CfrPlus && *regret_sum < 0.0 {
+            // Regret matching+: never let cumulative regret go negative, so a
+            // move that looked bad early on can recover as soon as it's good.
+            *regret_sum = 0.0;
+        }
     }
 
-    pub fn update_strategy(&mut self, legal_moves: &Vec<Move>, realization_weight: Floating) {
-        // compute strategies by regret matching
-        let mut normalizing_sum = 0.0;
-        for m in legal_moves {
-            let r = self.regret_sum.get(m).unwrap_or(&0.0);
-            let r_pos = if *r > 0.0 { *r } else { 0.0 };
-            self.strategy.insert(*m, r_pos);
-            normalizing_sum += r_pos;
-        }
+    pub fn update_strategy(
+        &mut self,
+        legal_moves: &Vec<Move>,
+        realization_weight: Floating,
+        iteration: Floating,
+        variant: CfrVariant,
+    ) {
+        self.strategy = regret_matching_strategy(&self.regret_sum, legal_moves);
+        let avg_weight = match variant {
+            CfrVariant::Vanilla => 1.0,
+            // Linear averaging: later iterations' strategies count for more,
+            // since they're computed from more-refined regrets.
+            CfrVariant::CfrPlus => iteration,
+        };
         for m in legal_moves {
-            let strat_m = if normalizing_sum > 0.0 {
-                self.strategy.get(m).unwrap() / normalizing_sum
-            } else {
-                1.0 / (legal_moves.len() as Floating)
-            };
-            self.strategy.insert(*m, strat_m);
-            let sum_update = realization_weight * strat_m;
+            let strat_m = *self.strategy.get(m).unwrap();
+            let sum_update = avg_weight * realization_weight * strat_m;
             self.strategy_sum
                 .entry(*m)
                 .and_modify(|s| *s += sum_update)
@@ -292,6 +440,52 @@ impl NodeInfo {
         }
     }
 
+    /// Add a raw (un-floored) regret increment, for accumulating a
+    /// thread-local delta to be merged into the shared node map later,
+    /// rather than updating `regret_sum` in place straight away.
+    pub fn add_regret(&mut self, m: Move, r: Floating) {
+        *self
+            .regret_sum
+            .get_mut(&m)
+            .expect("We should only call add_regret on legal moves") += r;
+    }
+
+    /// Add a raw strategy-sum increment, for the same delta-accumulation
+    /// purpose as [`NodeInfo::add_regret`].
+    pub fn add_strategy_sum(&mut self, m: Move, s: Floating) {
+        *self
+            .strategy_sum
+            .get_mut(&m)
+            .expect("We should only call add_strategy_sum on legal moves") += s;
+    }
+
+    /// Merge another node's (delta) regret and strategy sums into this one,
+    /// elementwise. Used to fold thread-local batch deltas into the shared
+    /// node map between parallel rounds.
+    pub fn merge(&mut self, other: &NodeInfo) {
+        for (m, r) in &other.regret_sum {
+            *self.regret_sum.entry(*m).or_insert(0.0) += r;
+        }
+        for (m, s) in &other.strategy_sum {
+            *self.strategy_sum.entry(*m).or_insert(0.0) += s;
+        }
+    }
+
+    /// Apply the CFR+ non-negative regret floor (if `variant` calls for it)
+    /// and refresh the cached regret-matching strategy. Called once per
+    /// parallel round, after merging all threads' deltas, since batches
+    /// accumulate raw regret via [`NodeInfo::add_regret`] without flooring.
+    pub fn finish_round(&mut self, legal_moves: &Vec<Move>, variant: CfrVariant) {
+        if variant == CfrVariant::CfrPlus {
+            for r in self.regret_sum.values_mut() {
+                if *r < 0.0 {
+                    *r = 0.0;
+                }
+            }
+        }
+        self.strategy = regret_matching_strategy(&self.regret_sum, legal_moves);
+    }
+
     pub fn get_average_strategy(&self, legal_moves: &Vec<Move>) -> HashMap<Move, Floating> {
         let mut avg_strategy: HashMap<Move, Floating> = HashMap::new();
         let mut normalizing_sum = 0.0;
@@ -312,6 +506,29 @@ impl NodeInfo {
     }
 }
 
+fn regret_matching_strategy(
+    regret_sum: &HashMap<Move, Floating>,
+    legal_moves: &Vec<Move>,
+) -> HashMap<Move, Floating> {
+    let mut strategy = HashMap::new();
+    let mut normalizing_sum = 0.0;
+    for m in legal_moves {
+        let r = regret_sum.get(m).unwrap_or(&0.0);
+        let r_pos = if *r > 0.0 { *r } else { 0.0 };
+        strategy.insert(*m, r_pos);
+        normalizing_sum += r_pos;
+    }
+    for m in legal_moves {
+        let strat_m = if normalizing_sum > 0.0 {
+            strategy.get(m).unwrap() / normalizing_sum
+        } else {
+            1.0 / (legal_moves.len() as Floating)
+        };
+        strategy.insert(*m, strat_m);
+    }
+    strategy
+}
+
 fn new_move_to_float_map_zeros(legal_moves: &Vec<Move>) -> HashMap<Move, Floating> {
     let mut new_map = HashMap::new();
     for m in legal_moves {